@@ -10,28 +10,128 @@ use bevy::{
 };
 use bevy_egui::{egui, EguiContext, EguiPlugin};
 use bevy_flycam::{FlyCam, NoCameraPlayerPlugin};
+use bevy_xpbd_3d::prelude::*;
 use rand::distributions::Distribution;
+use serde::{Deserialize, Serialize};
 
 pub mod instancing;
 use instancing::{CustomMaterialPlugin, InstanceData, InstanceMaterialData};
 
-type CellLocations = [bool; CELL_LOCATIONS_SIZE];
+type CellLocations = [u8; CELL_LOCATIONS_SIZE];
 
 type Paused = bool;
 
+// Optional "physics debris" mode: when a cell dies it spawns a falling rigid
+// body instead of simply vanishing.
+struct DebrisConfig {
+    enabled: bool,
+    lifetime: f32,
+}
+
+impl Default for DebrisConfig {
+    fn default() -> Self {
+        DebrisConfig {
+            enabled: false,
+            lifetime: 3.,
+        }
+    }
+}
+
+// Emitted from `cell_location_updater` for every cell that transitions from a
+// non-zero state to dead (state 0) while debris mode is on.
+struct CellDied {
+    position: Vec3,
+}
+
+// Marks a spawned debris cube so it can be despawned once its timer elapses.
+#[derive(Component)]
+struct Debris {
+    timer: Timer,
+}
+
 const GAME_SIZE: f32 = 100.;
 const CELL_LOCATIONS_SIZE: usize = (GAME_SIZE * GAME_SIZE * GAME_SIZE) as usize;
 const CELL_SIZE: f32 = 1.;
 
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Neighborhood {
+    Moore,
+    VonNeumann,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct GameRule {
     neighbors_to_surive: [bool; 27],
     neighbors_to_spawn: [bool; 27],
     spawn_noise_count: i32,
     spawn_noise_radius: i32,
+    states: u8,
+    neighborhood: Neighborhood,
     color_from: Color,
     color_to: Color,
 }
 
+// A serializable snapshot of a running simulation. We only persist the index and
+// state of every non-empty cell (alive or decaying) so a saved file stays small
+// instead of carrying the full million-element state array around.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    rule: GameRule,
+    alive: Vec<(usize, u8)>,
+}
+
+const SNAPSHOT_PATH: &str = "snapshot.json";
+
+impl Snapshot {
+    fn capture(rule: &GameRule, cell_locations: &CellLocations) -> Self {
+        let alive = cell_locations
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &state)| (state != 0).then_some((index, state)))
+            .collect();
+        Snapshot {
+            rule: rule.clone(),
+            alive,
+        }
+    }
+
+    // Validates the snapshot before mutating any state, so a hand-edited or
+    // corrupted file (this is shareable "seed" data, not trusted input) fails
+    // the same way an unparsable file does instead of corrupting the sim: an
+    // out-of-range `alive` index would panic indexing `cell_locations`, a
+    // `states < 2` rule would underflow `states - 1` on the very next
+    // `feed_cells`/`cell_location_updater` frame, and a stored `state` above
+    // `rule.states - 1` would silently blow the `[0, 1]` factor `feed_cells`
+    // interpolates colors with and decay for longer than normal play allows.
+    fn restore(self, cell_locations: &mut CellLocations) -> Result<GameRule, String> {
+        if self.rule.states < 2 {
+            return Err(format!(
+                "snapshot rule has {} states, need at least 2",
+                self.rule.states
+            ));
+        }
+        if let Some((index, _)) = self
+            .alive
+            .iter()
+            .find(|(index, _)| *index >= CELL_LOCATIONS_SIZE)
+        {
+            return Err(format!("snapshot cell index {} is out of bounds", index));
+        }
+        let max_state = self.rule.states - 1;
+        if let Some((index, state)) = self.alive.iter().find(|(_, state)| *state > max_state) {
+            return Err(format!(
+                "snapshot cell {} has state {}, rule only allows up to {}",
+                index, state, max_state
+            ));
+        }
+        *cell_locations = [0; CELL_LOCATIONS_SIZE];
+        for (index, state) in self.alive {
+            cell_locations[index] = state;
+        }
+        Ok(self.rule)
+    }
+}
+
 impl GameRule {
     pub fn default() -> Self {
         let neighbors_to_surive = Self::to_dense_array(&[5, 6, 7, 8]);
@@ -41,6 +141,8 @@ impl GameRule {
             neighbors_to_spawn,
             spawn_noise_count: 50000,
             spawn_noise_radius: 75,
+            states: 2,
+            neighborhood: Neighborhood::Moore,
             color_from: Color::YELLOW,
             color_to: Color::BLUE,
         }
@@ -53,10 +155,86 @@ impl GameRule {
         }
         ar
     }
+
+    // Parse a rule string of the form `S/B/C/N`, where S (survival) and B
+    // (birth) are comma-separated neighbor counts or inclusive `a-b` ranges, C
+    // is the generation/state count and N is `M` (Moore) or `N` (Von Neumann).
+    pub fn from_rule_string(s: &str) -> Option<GameRule> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 4 {
+            return None;
+        }
+        let neighborhood = match parts[3].trim() {
+            "M" | "m" => Neighborhood::Moore,
+            "N" | "n" => Neighborhood::VonNeumann,
+            _ => return None,
+        };
+        let mut rule = GameRule::default();
+        rule.neighbors_to_surive = Self::to_dense_array(&parse_rule_counts(parts[0])?);
+        rule.neighbors_to_spawn = Self::to_dense_array(&parse_rule_counts(parts[1])?);
+        let states: u8 = parts[2].trim().parse().ok()?;
+        // Mirror the states slider's invariant: fewer than two states leaves no
+        // room for a live state distinct from the dead sentinel and would
+        // underflow `states - 1` in the updater.
+        if states < 2 {
+            return None;
+        }
+        rule.states = states;
+        rule.neighborhood = neighborhood;
+        Some(rule)
+    }
+
+    // Render the current rule back out in the `S/B/C/N` notation.
+    pub fn to_rule_string(&self) -> String {
+        let counts = |ar: &[bool; 27]| {
+            ar.iter()
+                .enumerate()
+                .filter_map(|(i, &b)| b.then_some(i.to_string()))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let n = match self.neighborhood {
+            Neighborhood::Moore => "M",
+            Neighborhood::VonNeumann => "N",
+        };
+        format!(
+            "{}/{}/{}/{}",
+            counts(&self.neighbors_to_surive),
+            counts(&self.neighbors_to_spawn),
+            self.states,
+            n
+        )
+    }
+}
+
+// Expand a comma-separated list of counts and inclusive `a-b` ranges into the
+// individual neighbor counts they cover (e.g. `5-7,12` -> [5, 6, 7, 12]).
+fn parse_rule_counts(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    for part in s.split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        match part.split_once('-') {
+            Some((a, b)) => {
+                let a: u8 = a.trim().parse().ok()?;
+                let b: u8 = b.trim().parse().ok()?;
+                out.extend(a..=b);
+            }
+            None => out.push(part.parse().ok()?),
+        }
+    }
+    // A cell has at most 26 neighbors, so any count outside the dense array is
+    // invalid rather than an out-of-bounds panic in `to_dense_array`.
+    if out.iter().any(|&c| c >= 27) {
+        return None;
+    }
+    Some(out)
 }
 
 fn main() {
-    let cell_locations: CellLocations = [false; CELL_LOCATIONS_SIZE];
+    let cell_locations: CellLocations = [0; CELL_LOCATIONS_SIZE];
     let game_rule: GameRule = GameRule::default();
     let paused: Paused = true;
     App::new()
@@ -64,14 +242,24 @@ fn main() {
         .add_plugin(CustomMaterialPlugin)
         .add_plugin(NoCameraPlayerPlugin)
         .add_plugin(EguiPlugin)
+        .add_plugins(PhysicsPlugins)
+        .add_event::<CellDied>()
         .add_startup_system(setup)
         .add_system(cell_location_updater.with_run_criteria(FixedTimestep::step(0.125)))
         .add_system(ui.after(cell_location_updater))
         .add_system(feed_cells)
+        .add_system(cell_editor)
+        .add_system(
+            spawn_debris
+                .with_run_criteria(FixedTimestep::step(0.125))
+                .after(cell_location_updater),
+        )
+        .add_system(despawn_debris)
         .add_system(pause)
         .insert_resource(cell_locations)
         .insert_resource(game_rule)
         .insert_resource(paused)
+        .init_resource::<DebrisConfig>()
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_plugin(LogDiagnosticsPlugin::default())
         .run();
@@ -92,48 +280,77 @@ fn translate_index_to_location(index: usize) -> (f32, f32, f32) {
     (x, y, z)
 }
 
-fn get_neighbors(index: i32, cell_locations: &ResMut<CellLocations>) -> i32 {
+// All 26 Moore neighbors a cell can have.
+const MOORE_OFFSETS: [(f32, f32, f32); 26] = [
+    (-1., -1., -1.),
+    (0., -1., -1.),
+    (1., -1., -1.),
+    (-1., 0., -1.),
+    (0., 0., -1.),
+    (1., 0., -1.),
+    (-1., 1., -1.),
+    (0., 1., -1.),
+    (1., 1., -1.),
+    (-1., -1., 0.),
+    (0., -1., 0.),
+    (1., -1., 0.),
+    (-1., 0., 0.),
+    (1., 0., 0.),
+    (-1., 1., 0.),
+    (0., 1., 0.),
+    (1., 1., 0.),
+    (-1., -1., 1.),
+    (0., -1., 1.),
+    (1., -1., 1.),
+    (-1., 0., 1.),
+    (0., 0., 1.),
+    (1., 0., 1.),
+    (-1., 1., 1.),
+    (0., 1., 1.),
+    (1., 1., 1.),
+];
+
+// The six axis-aligned faces for a Von Neumann neighborhood.
+const VON_NEUMANN_OFFSETS: [(f32, f32, f32); 6] = [
+    (-1., 0., 0.),
+    (1., 0., 0.),
+    (0., -1., 0.),
+    (0., 1., 0.),
+    (0., 0., -1.),
+    (0., 0., 1.),
+];
+
+// Counts how many neighbors (Moore or Von Neumann, per `neighborhood`) are
+// currently at the max ("alive") state. Decaying cells (0 < state < max) are
+// deliberately ignored so they do not contribute to births or survival.
+// `out_of_bounds_is_solid` controls how a neighbor that falls off the grid is
+// treated: the normal rule update counts it as empty (`false`), while the
+// cave generator's smoothing pass wants the boundary to read as solid rock
+// (`true`) so caves close off against the grid edge instead of leaking open.
+fn get_neighbors(
+    index: i32,
+    cell_locations: &ResMut<CellLocations>,
+    max_state: u8,
+    neighborhood: Neighborhood,
+    out_of_bounds_is_solid: bool,
+) -> i32 {
     let loc = translate_index_to_location(index as usize);
-    // All potential neighbors a cell can have
-    let locations = [
-        (-1., -1., -1.),
-        (0., -1., -1.),
-        (1., -1., -1.),
-        (-1., 0., -1.),
-        (0., 0., -1.),
-        (1., 0., -1.),
-        (-1., 1., -1.),
-        (0., 1., -1.),
-        (1., 1., -1.),
-        (-1., -1., 0.),
-        (0., -1., 0.),
-        (1., -1., 0.),
-        (-1., 0., 0.),
-        (1., 0., 0.),
-        (-1., 1., 0.),
-        (0., 1., 0.),
-        (1., 1., 0.),
-        (-1., -1., 1.),
-        (0., -1., 1.),
-        (1., -1., 1.),
-        (-1., 0., 1.),
-        (0., 0., 1.),
-        (1., 0., 1.),
-        (-1., 1., 1.),
-        (0., 1., 1.),
-        (1., 1., 1.),
-    ];
+    let locations: &[(f32, f32, f32)] = match neighborhood {
+        Neighborhood::Moore => &MOORE_OFFSETS,
+        Neighborhood::VonNeumann => &VON_NEUMANN_OFFSETS,
+    };
     locations.iter().fold(0, |acc, x| {
         if loc.0.abs() + x.0 >= (GAME_SIZE / 2.) - 1.
             || loc.1.abs() + x.1 >= (GAME_SIZE / 2.) - 1.
             || loc.2.abs() + x.2 >= (GAME_SIZE / 2.) - 1.
         {
-            return acc;
+            return if out_of_bounds_is_solid { acc + 1 } else { acc };
         }
         let index = translate_location_to_index(loc.0 + x.0, loc.1 + x.1, loc.2 + x.2);
-        match cell_locations[index] {
-            true => acc + 1,
-            false => acc,
+        if cell_locations[index] == max_state {
+            acc + 1
+        } else {
+            acc
         }
     })
 }
@@ -142,35 +359,118 @@ fn cell_location_updater(
     mut cell_locations: ResMut<CellLocations>,
     game_rule: Res<GameRule>,
     paused: Res<Paused>,
+    debris: Res<DebrisConfig>,
+    mut cell_died: EventWriter<CellDied>,
 ) {
     if *paused {
         return;
     }
+    let max_state = game_rule.states - 1;
     let task_pool = TaskPool::new();
     let max_size = (GAME_SIZE * GAME_SIZE * GAME_SIZE) as i32;
     let chunck_size = ((GAME_SIZE * GAME_SIZE * GAME_SIZE) / 32.) as usize;
     let counts = (0..max_size).collect::<Vec<i32>>();
+    // Every cell's next state is derived from the prior frame, collected as a
+    // change list so all reads happen before any writes (a logical second
+    // buffer swapped in at the end).
     let cell_changes = counts.par_chunk_map(&task_pool, chunck_size, |chunck| {
-        let mut cells_to_add = Vec::new();
-        let mut cells_to_remove = Vec::new();
+        let mut changes = Vec::new();
         for i in chunck {
-            let nc = get_neighbors(*i, &cell_locations) as usize;
-            if game_rule.neighbors_to_spawn[nc] {
-                cells_to_add.push(*i as usize);
-            }
-            if !game_rule.neighbors_to_surive[nc] {
-                cells_to_remove.push(*i as usize);
+            let state = cell_locations[*i as usize];
+            let next = if state == 0 {
+                let nc = get_neighbors(
+                    *i,
+                    &cell_locations,
+                    max_state,
+                    game_rule.neighborhood,
+                    false,
+                ) as usize;
+                if game_rule.neighbors_to_spawn[nc] {
+                    max_state
+                } else {
+                    0
+                }
+            } else if state == max_state {
+                let nc = get_neighbors(
+                    *i,
+                    &cell_locations,
+                    max_state,
+                    game_rule.neighborhood,
+                    false,
+                ) as usize;
+                if game_rule.neighbors_to_surive[nc] {
+                    max_state
+                } else {
+                    state - 1
+                }
+            } else {
+                // Intermediate states unconditionally decay and cannot revive.
+                state - 1
+            };
+            if next != state {
+                changes.push((*i as usize, next));
             }
         }
-        (cells_to_add, cells_to_remove)
+        changes
     });
 
-    for (cells_to_add, cells_to_remove) in cell_changes {
-        for i in cells_to_add {
-            cell_locations[i] = true;
+    for changes in cell_changes {
+        for (i, next) in changes {
+            if debris.enabled && cell_locations[i] != 0 && next == 0 {
+                let (x, y, z) = translate_index_to_location(i);
+                cell_died.send(CellDied {
+                    position: Vec3::new(x, y, z),
+                });
+            }
+            cell_locations[i] = next;
         }
-        for i in cells_to_remove {
-            cell_locations[i] = false;
+    }
+}
+
+// Consumes `CellDied` events and spawns a short-lived dynamic cube for each, so
+// collapsing structures scatter physical debris that falls and collides.
+fn spawn_debris(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cell_died: EventReader<CellDied>,
+    debris: Res<DebrisConfig>,
+) {
+    let mut rng = rand::thread_rng();
+    let jitter = rand::distributions::Uniform::from(-2.0f32..2.0);
+    for ev in cell_died.iter() {
+        let velocity = Vec3::new(
+            jitter.sample(&mut rng),
+            jitter.sample(&mut rng),
+            jitter.sample(&mut rng),
+        );
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: CELL_SIZE })),
+                material: materials.add(Color::WHITE.into()),
+                transform: Transform::from_translation(ev.position),
+                ..default()
+            })
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::cuboid(CELL_SIZE, CELL_SIZE, CELL_SIZE))
+            .insert(LinearVelocity(velocity))
+            .insert(Debris {
+                timer: Timer::from_seconds(debris.lifetime, false),
+            });
+    }
+}
+
+// Ticks each debris cube's lifetime and despawns it once elapsed so the count
+// stays bounded.
+fn despawn_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_debris: Query<(Entity, &mut Debris)>,
+) {
+    for (entity, mut debris) in q_debris.iter_mut() {
+        debris.timer.tick(time.delta());
+        if debris.timer.finished() {
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -181,32 +481,138 @@ fn feed_cells(
     mut q_instances: Query<&mut InstanceMaterialData>,
 ) {
     let mut instances = q_instances.get_single_mut().unwrap();
+    let max_state = game_rule.states - 1;
     let x: Vec<InstanceData> = cell_locations
         .iter()
         .enumerate()
-        .filter_map(|(index, x)| match x {
-            false => None,
-            true => {
-                let loc = translate_index_to_location(index);
-                // let distance = (loc.0.abs() + loc.1.abs() + loc.2.abs()) / (GAME_SIZE * 1.5);
-                let distance = loc.0.abs().max(loc.1.abs()).max(loc.2.abs()) / (GAME_SIZE / 2.);
-                let r =
-                    (1. - distance) * game_rule.color_from.r() + distance * game_rule.color_to.r();
-                let g =
-                    (1. - distance) * game_rule.color_from.g() + distance * game_rule.color_to.g();
-                let b =
-                    (1. - distance) * game_rule.color_from.b() + distance * game_rule.color_to.b();
-                Some(InstanceData {
-                    position: Vec3::new(loc.0, loc.1, loc.2),
-                    scale: 1.,
-                    color: [r, g, b, 1.],
-                })
+        .filter_map(|(index, &state)| {
+            if state == 0 {
+                return None;
             }
+            let loc = translate_index_to_location(index);
+            // Fade from color_from up to color_to as a cell's state climbs to
+            // the max, so decaying cells visibly dim out.
+            let factor = state as f32 / max_state as f32;
+            let r = (1. - factor) * game_rule.color_from.r() + factor * game_rule.color_to.r();
+            let g = (1. - factor) * game_rule.color_from.g() + factor * game_rule.color_to.g();
+            let b = (1. - factor) * game_rule.color_from.b() + factor * game_rule.color_to.b();
+            Some(InstanceData {
+                position: Vec3::new(loc.0, loc.1, loc.2),
+                scale: 1.,
+                color: [r, g, b, factor],
+            })
         })
         .collect();
     *instances = InstanceMaterialData(x);
 }
 
+// Walk the voxel grid along the cursor ray with the Amanatides-Woo DDA and
+// toggle cells like placing/removing blocks in a voxel game. Left click places
+// a cell against the face of the first non-empty (alive or decaying) voxel hit,
+// right click erases that voxel.
+fn cell_editor(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+    game_rule: Res<GameRule>,
+    mut cell_locations: ResMut<CellLocations>,
+) {
+    let place = mouse.just_pressed(MouseButton::Left);
+    let erase = mouse.just_pressed(MouseButton::Right);
+    if !place && !erase {
+        return;
+    }
+    // Clicks on the egui panel (Save/Load, sliders, checkboxes, ...) also show
+    // up in `Input<MouseButton>`, so bail out before raycasting whenever egui
+    // is the one consuming the pointer.
+    if egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let (camera, camera_transform) = match q_camera.get_single() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let window = match windows.get_primary() {
+        Some(w) => w,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(c) => c,
+        None => return,
+    };
+
+    // Reconstruct a world-space ray from the cursor position.
+    let screen_size = Vec2::new(window.width(), window.height());
+    let ndc = (cursor / screen_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(f32::EPSILON));
+    let origin = camera_transform.translation();
+    let dir = (far - near).normalize();
+
+    let half = GAME_SIZE / 2.;
+    let in_bounds = |v: Vec3| {
+        v.x >= -half && v.x < half && v.y >= -half && v.y < half && v.z >= -half && v.z < half
+    };
+
+    // Amanatides-Woo setup: starting voxel, per-axis step, t_delta and t_max.
+    let mut voxel = (origin / CELL_SIZE).floor() * CELL_SIZE;
+    let step = Vec3::new(dir.x.signum(), dir.y.signum(), dir.z.signum());
+    let t_delta = Vec3::new(
+        CELL_SIZE / dir.x.abs(),
+        CELL_SIZE / dir.y.abs(),
+        CELL_SIZE / dir.z.abs(),
+    );
+    let boundary = |o: f32, v: f32, d: f32, s: f32| {
+        if d == 0. {
+            f32::INFINITY
+        } else {
+            let next = if s > 0. { v + CELL_SIZE } else { v };
+            (next - o) / d
+        }
+    };
+    let mut t_max = Vec3::new(
+        boundary(origin.x, voxel.x, dir.x, step.x),
+        boundary(origin.y, voxel.y, dir.y, step.y),
+        boundary(origin.z, voxel.z, dir.z, step.z),
+    );
+
+    let max_distance = GAME_SIZE * 2.;
+    let mut last_empty: Option<usize> = None;
+    let mut distance = 0.;
+    while distance <= max_distance {
+        if in_bounds(voxel) {
+            let index = translate_location_to_index(voxel.x, voxel.y, voxel.z);
+            if cell_locations[index] != 0 {
+                if erase {
+                    cell_locations[index] = 0;
+                } else if let Some(empty) = last_empty {
+                    cell_locations[empty] = game_rule.states - 1;
+                }
+                return;
+            }
+            last_empty = Some(index);
+        }
+
+        // Advance along whichever axis reaches the next voxel boundary first.
+        if t_max.x < t_max.y && t_max.x < t_max.z {
+            voxel.x += step.x * CELL_SIZE;
+            distance = t_max.x;
+            t_max.x += t_delta.x;
+        } else if t_max.y < t_max.z {
+            voxel.y += step.y * CELL_SIZE;
+            distance = t_max.y;
+            t_max.y += t_delta.y;
+        } else {
+            voxel.z += step.z * CELL_SIZE;
+            distance = t_max.z;
+            t_max.z += t_delta.z;
+        }
+    }
+}
+
 fn pause(mut key_evr: EventReader<KeyboardInput>, mut paused: ResMut<bool>) {
     for ev in key_evr.iter() {
         if ButtonState::Pressed == ev.state && ev.scan_code == 28 {
@@ -256,12 +662,84 @@ fn create_random_spawn_points(
         .collect()
 }
 
+// Tunables for the cave generator, kept as a `Local` in `ui` so they persist
+// between frames without polluting the shared `GameRule`.
+struct CaveParams {
+    p: f32,
+    k: i32,
+    t: i32,
+}
+
+impl Default for CaveParams {
+    fn default() -> Self {
+        CaveParams {
+            p: 0.45,
+            k: 4,
+            t: 13,
+        }
+    }
+}
+
+// Counts solid Moore neighbors via `get_neighbors`, with out-of-bounds cells
+// treated as solid so generated caves close off against the grid boundary.
+fn solid_moore_neighbors(index: i32, cell_locations: &ResMut<CellLocations>, max_state: u8) -> i32 {
+    get_neighbors(index, cell_locations, max_state, Neighborhood::Moore, true)
+}
+
+// Seed the grid with organic cave-like blobs: a random fill inside the spawn
+// radius followed by `k` synchronous smoothing passes. The result is left for
+// the caller to keep static or release into the normal rule evolution.
+fn generate_caves(
+    cell_locations: &mut ResMut<CellLocations>,
+    game_rule: &GameRule,
+    params: &CaveParams,
+) {
+    let max_state = game_rule.states - 1;
+    let r = (game_rule.spawn_noise_radius / 2).clamp(0, (GAME_SIZE / 2.) as i32);
+
+    **cell_locations = [0; CELL_LOCATIONS_SIZE];
+    let mut rng = rand::thread_rng();
+    let distro = rand::distributions::Uniform::from(0.0f32..1.0);
+    for z in -r..r {
+        for y in -r..r {
+            for x in -r..r {
+                if distro.sample(&mut rng) < params.p {
+                    let index = translate_location_to_index(x as f32, y as f32, z as f32);
+                    cell_locations[index] = max_state;
+                }
+            }
+        }
+    }
+
+    for _ in 0..params.k {
+        let mut changes = Vec::new();
+        for z in -r..r {
+            for y in -r..r {
+                for x in -r..r {
+                    let index = translate_location_to_index(x as f32, y as f32, z as f32);
+                    let solid = solid_moore_neighbors(index as i32, &*cell_locations, max_state);
+                    let next = if solid >= params.t { max_state } else { 0 };
+                    if cell_locations[index] != next {
+                        changes.push((index, next));
+                    }
+                }
+            }
+        }
+        for (index, next) in changes {
+            cell_locations[index] = next;
+        }
+    }
+}
+
 fn ui(
     mut egui_context: ResMut<EguiContext>,
     q_instances: Query<&InstanceMaterialData>,
     mut game_rule: ResMut<GameRule>,
     mut cell_locations: ResMut<CellLocations>,
     mut paused: ResMut<Paused>,
+    mut debris: ResMut<DebrisConfig>,
+    mut rule_text: Local<String>,
+    mut cave: Local<CaveParams>,
 ) {
     let instances = q_instances.get_single().unwrap();
     egui::Window::new("Celluar!").show(egui_context.ctx_mut(), |ui| {
@@ -272,9 +750,38 @@ fn ui(
             ui.checkbox(&mut paused, "Paused");
 
             if ui.button("reset").clicked() {
-                *cell_locations = [false; CELL_LOCATIONS_SIZE];
+                *cell_locations = [0; CELL_LOCATIONS_SIZE];
             }
 
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    let snapshot = Snapshot::capture(&game_rule, &cell_locations);
+                    match serde_json::to_string(&snapshot)
+                        .map_err(|e| e.to_string())
+                        .and_then(|s| std::fs::write(SNAPSHOT_PATH, s).map_err(|e| e.to_string()))
+                    {
+                        Ok(()) => info!("saved snapshot to {}", SNAPSHOT_PATH),
+                        Err(e) => error!("failed to save snapshot: {}", e),
+                    }
+                }
+
+                if ui.button("Load").clicked() {
+                    match std::fs::read_to_string(SNAPSHOT_PATH)
+                        .map_err(|e| e.to_string())
+                        .and_then(|s| {
+                            serde_json::from_str::<Snapshot>(&s).map_err(|e| e.to_string())
+                        })
+                        .and_then(|snapshot| snapshot.restore(&mut cell_locations))
+                    {
+                        Ok(rule) => {
+                            *game_rule = rule;
+                            info!("loaded snapshot from {}", SNAPSHOT_PATH);
+                        }
+                        Err(e) => error!("failed to load snapshot: {}", e),
+                    }
+                }
+            });
+
             if ui.button("spawn noise").clicked() {
                 for t in create_random_spawn_points(
                     game_rule.spawn_noise_count,
@@ -282,7 +789,7 @@ fn ui(
                     game_rule.spawn_noise_radius,
                 ) {
                     let index = translate_location_to_index(t.0, t.1, t.2);
-                    cell_locations[index] = true;
+                    cell_locations[index] = game_rule.states - 1;
                 }
             }
             let mut spawn_noise_count = game_rule.spawn_noise_count as f32;
@@ -296,6 +803,21 @@ fn ui(
                 egui::Slider::new(&mut spawn_noise_radius, 1.0..=100.0).text("raduis to spawn in"),
             );
             game_rule.spawn_noise_radius = spawn_noise_radius as i32;
+
+            if ui.button("generate caves").clicked() {
+                generate_caves(&mut cell_locations, &game_rule, &cave);
+                *paused = true;
+            }
+            ui.add(egui::Slider::new(&mut cave.p, 0.0..=1.0).text("cave fill probability"));
+            let mut cave_iterations = cave.k as f32;
+            ui.add(egui::Slider::new(&mut cave_iterations, 0.0..=20.0).text("cave iterations"));
+            cave.k = cave_iterations as i32;
+            let mut cave_threshold = cave.t as f32;
+            ui.add(egui::Slider::new(&mut cave_threshold, 0.0..=26.0).text("cave wall threshold"));
+            cave.t = cave_threshold as i32;
+
+            ui.checkbox(&mut debris.enabled, "Physics debris");
+            ui.add(egui::Slider::new(&mut debris.lifetime, 0.1..=10.0).text("debris lifetime"));
         }
 
         ui.add_space(24.0);
@@ -304,16 +826,54 @@ fn ui(
             color_picker(ui, &mut game_rule.color_from);
             color_picker(ui, &mut game_rule.color_to);
 
+            let mut states = game_rule.states as f32;
+            ui.add(egui::Slider::new(&mut states, 2.0..=255.0).text("states"));
+            game_rule.states = states as u8;
+
+            ui.label(format!("Current rule: {}", game_rule.to_rule_string()));
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut *rule_text);
+                if ui.button("Apply rule string").clicked() {
+                    match GameRule::from_rule_string(&rule_text) {
+                        Some(parsed) => {
+                            game_rule.neighbors_to_surive = parsed.neighbors_to_surive;
+                            game_rule.neighbors_to_spawn = parsed.neighbors_to_spawn;
+                            game_rule.states = parsed.states;
+                            game_rule.neighborhood = parsed.neighborhood;
+                        }
+                        None => error!("could not parse rule string: {}", *rule_text),
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut game_rule.neighborhood, Neighborhood::Moore, "Moore");
+                ui.selectable_value(
+                    &mut game_rule.neighborhood,
+                    Neighborhood::VonNeumann,
+                    "Von Neumann",
+                );
+            });
+
+            // Von Neumann only ever reads neighbor counts 0..=6, so don't render
+            // checkboxes that can never affect the rule.
+            let shown = match game_rule.neighborhood {
+                Neighborhood::Moore => 27,
+                Neighborhood::VonNeumann => 7,
+            };
+
             ui.label("Survival Rule: ");
             ui.horizontal_wrapped(|ui| {
-                for (index, mut i) in game_rule.neighbors_to_surive.iter_mut().enumerate() {
+                for (index, mut i) in game_rule.neighbors_to_surive.iter_mut().take(shown).enumerate()
+                {
                     ui.checkbox(&mut i, format!("{}", index));
                 }
             });
 
             ui.label("Spawn Rule: ");
             ui.horizontal_wrapped(|ui| {
-                for (index, mut i) in game_rule.neighbors_to_spawn.iter_mut().enumerate() {
+                for (index, mut i) in game_rule.neighbors_to_spawn.iter_mut().take(shown).enumerate()
+                {
                     ui.checkbox(&mut i, format!("{}", index));
                 }
             });
@@ -324,6 +884,7 @@ fn ui(
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
+    game_rule: Res<GameRule>,
     mut cell_locations: ResMut<CellLocations>,
 ) {
     commands.spawn().insert_bundle((
@@ -349,6 +910,58 @@ fn setup(
 
     for t in create_random_spawn_points(1000, (0, 0, 0), 20) {
         let index = translate_location_to_index(t.0, t.1, t.2);
-        cell_locations[index] = true;
+        cell_locations[index] = game_rule.states - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rule_counts_expands_ranges_and_singles() {
+        assert_eq!(parse_rule_counts("5-7,12"), Some(vec![5, 6, 7, 12]));
+        assert_eq!(parse_rule_counts(""), Some(vec![]));
+        assert_eq!(parse_rule_counts(" 1 , 2-3 "), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn parse_rule_counts_rejects_out_of_range() {
+        assert_eq!(parse_rule_counts("27"), None);
+        assert_eq!(parse_rule_counts("20-30"), None);
+    }
+
+    #[test]
+    fn parse_rule_counts_rejects_garbage() {
+        assert_eq!(parse_rule_counts("a"), None);
+        assert_eq!(parse_rule_counts("1-"), None);
+    }
+
+    #[test]
+    fn from_rule_string_accepts_case_insensitive_neighborhood() {
+        let moore = GameRule::from_rule_string("5-8/6,7,9/2/M").unwrap();
+        assert_eq!(moore.neighborhood, Neighborhood::Moore);
+        let von_neumann = GameRule::from_rule_string("2-3/3/2/n").unwrap();
+        assert_eq!(von_neumann.neighborhood, Neighborhood::VonNeumann);
+    }
+
+    #[test]
+    fn from_rule_string_rejects_fewer_than_two_states() {
+        assert!(GameRule::from_rule_string("5-8/6,7,9/1/M").is_none());
+        assert!(GameRule::from_rule_string("5-8/6,7,9/0/M").is_none());
+    }
+
+    #[test]
+    fn from_rule_string_rejects_malformed_input() {
+        assert!(GameRule::from_rule_string("5-8/6,7,9/2").is_none());
+        assert!(GameRule::from_rule_string("5-8/6,7,9/2/X").is_none());
+        assert!(GameRule::from_rule_string("5-8/6,7,9/abc/M").is_none());
+    }
+
+    #[test]
+    fn rule_string_round_trips() {
+        let original = "5,6,7,8/6,7,9/3/M";
+        let rule = GameRule::from_rule_string(original).unwrap();
+        assert_eq!(rule.to_rule_string(), original);
     }
 }